@@ -1,11 +1,13 @@
 use std::fmt::{Debug, Display, Error, Formatter};
 
+use crate::error::Position;
+
 #[derive(Clone, Debug)]
 pub enum AstType {
     Number(f64),
     Ident(String),
-    Func(String, Box<AstNode>),
-    Binary(char, Box<AstNode>, Box<AstNode>),
+    Func(String, Vec<AstNode>),
+    Binary(String, Box<AstNode>, Box<AstNode>),
     Prefix(char, Box<AstNode>),
     Postfix(char, Box<AstNode>),
     Parens(Box<AstNode>),
@@ -15,11 +17,11 @@ use self::AstType::*;
 #[derive(Clone)]
 pub struct AstNode {
     pub typ: AstType,
-    pub pos: u32,
+    pub pos: Position,
 }
 
 impl AstNode {
-    pub fn new(typ: AstType, pos: u32) -> AstNode {
+    pub fn new(typ: AstType, pos: Position) -> AstNode {
         AstNode { typ: typ, pos: pos }
     }
 
@@ -50,8 +52,17 @@ impl<'a> Display for AsciiMathFmt<'a> {
         match &self.0.typ {
             &Number(n) => Display::fmt(&n, f),
             &Ident(ref s) => Display::fmt(&s, f),
-            &Func(ref s, ref arg) => f.write_fmt(format_args!("{}({})", s, arg)),
-            &Binary(ch, ref arg1, ref arg2) => f.write_fmt(format_args!("{}{}{}", arg1, ch, arg2)),
+            &Func(ref s, ref args) => {
+                try!(f.write_fmt(format_args!("{}(", s)));
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        try!(f.write_str(","));
+                    }
+                    try!(Display::fmt(arg, f));
+                }
+                f.write_str(")")
+            }
+            &Binary(ref op, ref arg1, ref arg2) => f.write_fmt(format_args!("{}{}{}", arg1, op, arg2)),
             &Prefix(ch, ref arg) => f.write_fmt(format_args!("{}{}", ch, arg)),
             &Postfix(ch, ref arg) => f.write_fmt(format_args!("{}{}", arg, ch)),
             &Parens(ref arg) => f.write_fmt(format_args!("({})", arg)),
@@ -69,17 +80,21 @@ impl<'a> TreeFmt<'a> {
     fn format(&self, f: &mut Formatter, indent: usize) -> Result<(), Error> {
         const INDENT: usize = 2;
 
-        // Output the line position and the indent.
-        try!(f.write_fmt(format_args!("{:3}:{:width$} ", self.0.pos, "", width=indent)));
+        // Output the line:column position and the indent.
+        let pos = self.0.pos;
+        try!(f.write_fmt(format_args!("{:>3}:{:<3}{:width$} ", pos.line, pos.col, "", width=indent)));
         match &self.0.typ {
             &Number(n) => f.write_fmt(format_args!("{}\n", n)),
             &Ident(ref s) => f.write_fmt(format_args!("{}\n", s)),
-            &Func(ref s, ref arg) => {
+            &Func(ref s, ref args) => {
                 try!(f.write_fmt(format_args!("{}()\n", s)));
-                arg.as_tree().format(f, indent + INDENT)
+                for arg in args {
+                    try!(arg.as_tree().format(f, indent + INDENT));
+                }
+                Ok(())
             }
-            &Binary(ch, ref arg1, ref arg2) => {
-                try!(f.write_fmt(format_args!("{}\n", ch)));
+            &Binary(ref op, ref arg1, ref arg2) => {
+                try!(f.write_fmt(format_args!("{}\n", op)));
                 try!(arg1.as_tree().format(f, indent + INDENT));
                 arg2.as_tree().format(f, indent + INDENT)
             }
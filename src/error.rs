@@ -1,13 +1,99 @@
 use std::num::ParseFloatError;
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+/// A position in the input, tracking the byte offset as well as the 1-based line and column so
+/// diagnostics on multi-line formulas point at the right place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    pub offset: u32,
+    pub line: u32,
+    pub col: u32,
+}
+
+impl Position {
+    /// The position at the very start of the input (line 1, column 1).
+    pub fn start() -> Position {
+        Position { offset: 0, line: 1, col: 1 }
+    }
+
+    /// Advances past `ch`, bumping the line and resetting the column on a newline.
+    pub fn advance(&mut self, ch: char) {
+        self.offset += ch.len_utf8() as u32;
+        if ch == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+    }
+}
+
+/// A contiguous range of the input, used to underline the offending text of a diagnostic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub len: u32,
+}
+
+impl Span {
+    pub fn new(start: Position, len: u32) -> Span {
+        Span { start: start, len: len }
+    }
+
+    /// A single-character span at `pos`.
+    pub fn at(pos: Position) -> Span {
+        Span { start: pos, len: 1 }
+    }
+}
+
+#[derive(Clone, Debug, Error)]
 pub enum ParseError {
-    #[error("{0}")]
-    Lex(String),
-    #[error("{0}")]
-    Parse(String),
+    #[error("Unexpected '{ch}' at line {} column {}", .pos.line, .pos.col)]
+    UnexpectedChar { ch: char, pos: Position },
+    #[error("{}", unexpected_token_msg(.found, .expected, .span))]
+    UnexpectedToken {
+        found: String,
+        expected: Option<String>,
+        span: Span,
+    },
+    #[error("Unbalanced parenthesis at line {} column {}", .pos.line, .pos.col)]
+    UnbalancedParen { pos: Position },
     #[error("{0}")]
     Float(#[from] ParseFloatError),
 }
 
+fn unexpected_token_msg(found: &str, expected: &Option<String>, span: &Span) -> String {
+    match expected {
+        Some(expected) => format!(
+            "Expected {}, but got {} at line {} column {}",
+            expected, found, span.start.line, span.start.col
+        ),
+        None => format!(
+            "Unexpected token {} at line {} column {}",
+            found, span.start.line, span.start.col
+        ),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum EvalError {
+    #[error("unbound variable '{0}'")]
+    UnboundVar(String),
+    #[error("unknown function '{0}'")]
+    UnknownFunc(String),
+    #[error("'{func}' expects {expected} argument(s), but got {found}")]
+    Arity {
+        func: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("factorial of {0} is undefined (requires a non-negative integer)")]
+    Factorial(f64),
+    #[error("division by zero")]
+    DivideByZero,
+    #[error("unknown operator '{0}'")]
+    UnknownOp(char),
+    #[error("unknown binary operator '{0}'")]
+    UnknownBinaryOp(String),
+}
+
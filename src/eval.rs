@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use crate::ast::AstNode;
+use crate::ast::AstType::*;
+use crate::error::EvalError;
+
+/// Signature of a function that can be registered in an [`Env`] and invoked from a `Func` node.
+pub type MathFn = fn(&[f64]) -> Result<f64, EvalError>;
+
+/// Evaluation environment holding variable bindings and callable functions.
+///
+/// Construct with [`Env::default`] to get the usual mathematical built-ins (`log`, `sin`, `cos`,
+/// `sqrt`) pre-registered, or [`Env::empty`] for a bare environment.
+#[derive(Clone)]
+pub struct Env {
+    vars: HashMap<String, f64>,
+    funcs: HashMap<String, MathFn>,
+}
+
+impl Env {
+    /// Creates an environment with no variables and no functions registered.
+    pub fn empty() -> Env {
+        Env {
+            vars: HashMap::new(),
+            funcs: HashMap::new(),
+        }
+    }
+
+    /// Binds (or rebinds) a variable to a value.
+    pub fn set_var(&mut self, name: &str, value: f64) {
+        self.vars.insert(name.to_string(), value);
+    }
+
+    /// Registers (or replaces) a callable function.
+    pub fn set_func(&mut self, name: &str, func: MathFn) {
+        self.funcs.insert(name.to_string(), func);
+    }
+
+    fn var(&self, name: &str) -> Result<f64, EvalError> {
+        self.vars
+            .get(name)
+            .cloned()
+            .ok_or_else(|| EvalError::UnboundVar(name.to_string()))
+    }
+
+    fn func(&self, name: &str) -> Result<MathFn, EvalError> {
+        self.funcs
+            .get(name)
+            .cloned()
+            .ok_or_else(|| EvalError::UnknownFunc(name.to_string()))
+    }
+}
+
+impl Default for Env {
+    fn default() -> Env {
+        let mut env = Env::empty();
+        env.set_func("log", |args| Ok(unary(args, "log")?.ln()));
+        env.set_func("sin", |args| Ok(unary(args, "sin")?.sin()));
+        env.set_func("cos", |args| Ok(unary(args, "cos")?.cos()));
+        env.set_func("sqrt", |args| Ok(unary(args, "sqrt")?.sqrt()));
+        env
+    }
+}
+
+/// Extracts the sole argument of a single-argument function, erroring on any other arity.
+fn unary(args: &[f64], name: &str) -> Result<f64, EvalError> {
+    match args {
+        [x] => Ok(*x),
+        _ => Err(EvalError::Arity {
+            func: name.to_string(),
+            expected: 1,
+            found: args.len(),
+        }),
+    }
+}
+
+/// Maps a boolean result to the numeric convention used for comparison/logical operators.
+fn bool_val(b: bool) -> f64 {
+    if b {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Computes `n!` for a non-negative integer operand, erroring otherwise.
+fn factorial(x: f64) -> Result<f64, EvalError> {
+    const EPSILON: f64 = 1e-9;
+    if x < 0.0 || (x - x.round()).abs() > EPSILON {
+        return Err(EvalError::Factorial(x));
+    }
+    let n = x.round() as u64;
+    Ok((1..=n).fold(1.0, |acc, i| acc * i as f64))
+}
+
+/// Evaluates `node` to a numeric result within the given environment.
+pub fn eval(node: &AstNode, env: &Env) -> Result<f64, EvalError> {
+    match &node.typ {
+        &Number(n) => Ok(n),
+        &Ident(ref s) => env.var(s),
+        &Parens(ref arg) => eval(arg, env),
+        &Func(ref s, ref args) => {
+            let func = env.func(s)?;
+            let mut vals = Vec::with_capacity(args.len());
+            for arg in args {
+                vals.push(eval(arg, env)?);
+            }
+            func(&vals)
+        }
+        &Prefix(ch, ref arg) => {
+            let val = eval(arg, env)?;
+            match ch {
+                '-' => Ok(-val),
+                _ => Err(EvalError::UnknownOp(ch)),
+            }
+        }
+        &Postfix(ch, ref arg) => {
+            let val = eval(arg, env)?;
+            match ch {
+                '!' => factorial(val),
+                _ => Err(EvalError::UnknownOp(ch)),
+            }
+        }
+        &Binary(ref op, ref arg1, ref arg2) => {
+            let lhs = eval(arg1, env)?;
+            let rhs = eval(arg2, env)?;
+            match op.as_str() {
+                "+" => Ok(lhs + rhs),
+                "-" => Ok(lhs - rhs),
+                "*" => Ok(lhs * rhs),
+                "/" => {
+                    if rhs == 0.0 {
+                        Err(EvalError::DivideByZero)
+                    } else {
+                        Ok(lhs / rhs)
+                    }
+                }
+                "^" => Ok(lhs.powf(rhs)),
+                // Comparison and logical operators yield 1.0 for true and 0.0 for false.
+                "==" => Ok(bool_val(lhs == rhs)),
+                "!=" => Ok(bool_val(lhs != rhs)),
+                "<=" => Ok(bool_val(lhs <= rhs)),
+                ">=" => Ok(bool_val(lhs >= rhs)),
+                "&&" => Ok(bool_val(lhs != 0.0 && rhs != 0.0)),
+                "||" => Ok(bool_val(lhs != 0.0 || rhs != 0.0)),
+                _ => Err(EvalError::UnknownBinaryOp(op.clone())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{eval, Env};
+    use crate::shuntingyard::parse;
+
+    fn eval_str(text: &str, env: &Env) -> f64 {
+        eval(&parse(text).unwrap(), env).unwrap()
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let env = Env::default();
+        assert_eq!(eval_str("1+2*3", &env), 7.0);
+        assert_eq!(eval_str("(1+2)*3", &env), 9.0);
+        assert_eq!(eval_str("2^3^2", &env), 512.0);
+        assert_eq!(eval_str("-3!", &env), -6.0);
+        assert_eq!(eval_str("4!", &env), 24.0);
+    }
+
+    #[test]
+    fn test_vars_and_funcs() {
+        let mut env = Env::default();
+        env.set_var("x", 9.0);
+        assert_eq!(eval_str("sqrt(x)", &env), 3.0);
+        assert_eq!(eval_str("cos(0)", &env), 1.0);
+    }
+
+    #[test]
+    fn test_multi_arg_funcs() {
+        let mut env = Env::default();
+        env.set_func("max", |args| Ok(args.iter().cloned().fold(f64::NEG_INFINITY, f64::max)));
+        env.set_func("pi", |_| Ok(std::f64::consts::PI));
+        assert_eq!(eval_str("max(1, 5, 3)", &env), 5.0);
+        assert_eq!(eval_str("pi()", &env), std::f64::consts::PI);
+    }
+
+    #[test]
+    fn test_errors() {
+        let env = Env::default();
+        assert!(eval(&parse("y+1").unwrap(), &env).is_err());
+        assert!(eval(&parse("1/0").unwrap(), &env).is_err());
+        assert!(eval(&parse("2.5!").unwrap(), &env).is_err());
+    }
+}
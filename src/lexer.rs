@@ -1,19 +1,20 @@
 use regex::Regex;
-use std::error::Error as StdError; // for .description()
 use std::iter;
 use std::str::FromStr;
 
-use crate::error::ParseError;
+use crate::error::{ParseError, Position};
 
 // IMPORTANT: This will not work performantly if it's called from any thread different than its
 // first invocation (since Regex optimizes for the first thread).
 lazy_static! {
     static ref REGEX_NUMBER: Regex = Regex::new(r"^[+-]?\d+(?:\.\d*)?(?:[eE]\d+)?").unwrap();
-    static ref REGEX_IDENT: Regex = Regex::new(r"^[a-zA-Z]+").unwrap();
+    static ref REGEX_IDENT: Regex = Regex::new(r"^[a-zA-Z][a-zA-Z0-9]*").unwrap();
 }
 
-// These two must be in the same order.
-const OPS_SINGLE: [char; 9] = ['+', '-', '*', '/', '^', '!', '=', '(', ')'];
+const OPS_SINGLE: [char; 10] = ['+', '-', '*', '/', '^', '!', '=', ',', '(', ')'];
+
+// Two-character operators, checked via maximal munch before the single-char table.
+const OPS_MULTI: [&str; 6] = ["==", "!=", "<=", ">=", "&&", "||"];
 
 /// Types of tokens.
 #[derive(Debug, PartialEq)]
@@ -21,15 +22,16 @@ pub enum TokenType<'a> {
     Number(f64),
     Ident(&'a str),
     OpSingle(char),
+    OpMulti(&'a str),
     End,
 }
 use self::TokenType::*;
 
-/// Token type with a text position number.
+/// Token type with a source position.
 #[derive(Debug)]
 pub struct Token<'a> {
     pub typ: TokenType<'a>,
-    pub pos: u32,
+    pub pos: Position,
 }
 
 impl<'a> PartialEq for Token<'a> {
@@ -54,8 +56,8 @@ impl<'a> PartialEq<Token<'a>> for TokenType<'a> {
 #[derive(Clone)]
 pub struct Lexer<'a> {
     text: &'a str,
-    pos: u32,
-    error: Option<String>,
+    pos: Position,
+    error: Option<ParseError>,
 }
 
 impl<'a> Lexer<'a> {
@@ -63,22 +65,28 @@ impl<'a> Lexer<'a> {
     pub fn new<'b>(text: &'b str) -> Lexer<'b> {
         Lexer {
             text: text,
-            pos: 0,
+            pos: Position::start(),
             error: None,
         }
     }
 
+    /// Consumes `n_bytes` of input, advancing the position over each `char`.
+    fn advance(&mut self, n_bytes: usize) {
+        for ch in self.text[..n_bytes].chars() {
+            self.pos.advance(ch);
+        }
+        self.text = &self.text[n_bytes..];
+    }
+
     /// Returns the current token, or an error if there was a lexing error.
     /// Subsequent invocations after an error, return a generic error.
     pub fn next_token(&mut self) -> Result<Token<'a>, ParseError> {
-        if let Some(ref msg) = self.error {
-            Err(ParseError::Lex(msg.clone()))
+        if let Some(ref err) = self.error {
+            Err(err.clone())
         } else {
             let res = self.next_token_();
             if let Err(ref err) = res {
-                let mut msg = "Errored previously: ".to_owned();
-                msg.push_str(err.description());
-                self.error = Some(msg);
+                self.error = Some(err.clone());
             }
             res
         }
@@ -99,46 +107,74 @@ impl<'a> Lexer<'a> {
                     }
                     Some(ch) => ch,
                 };
-                // Skip whitespace and increment pos
+                // Skip whitespace and advance the position
                 if !ch.is_whitespace() {
                     break;
                 }
-                self.text = &self.text[1..];
-                self.pos += 1;
+                self.pos.advance(ch);
+                self.text = &self.text[ch.len_utf8()..];
             }
         }
 
+        // Maximal munch: prefer a two-character operator over the single-char table.
+        if let Some(op) = OPS_MULTI.iter().find(|op| self.text.starts_with(**op)) {
+            let token = Token {
+                typ: OpMulti(&self.text[..op.len()]),
+                pos: self.pos,
+            };
+            self.advance(op.len());
+            return Ok(token);
+        }
+
         // Check single-character tokens.
-        // NOTE: Relocate this to the end if any of these become prefixes of longer tokens.
         if OPS_SINGLE.contains(&ch) {
             let token = Token {
                 typ: OpSingle(ch),
                 pos: self.pos,
             };
-            self.text = &self.text[1..];
-            self.pos += 1;
+            self.advance(ch.len_utf8());
             Ok(token)
         } else if let Some((0, n)) = REGEX_NUMBER.find(self.text) {
             let token = Token {
                 typ: Number(FromStr::from_str(&self.text[..n])?),
                 pos: self.pos,
             };
-            self.text = &self.text[n..];
-            self.pos += n as u32;
+            self.advance(n);
             Ok(token)
         } else if let Some((0, n)) = REGEX_IDENT.find(self.text) {
             let token = Token {
                 typ: Ident(&self.text[..n]),
                 pos: self.pos,
             };
-            self.text = &self.text[n..];
-            self.pos += n as u32;
+            self.advance(n);
             Ok(token)
         } else {
-            Err(ParseError::Lex(format!(
-                "Unexpected '{}' at position {}",
-                ch, self.pos
-            )))
+            Err(ParseError::UnexpectedChar {
+                ch: ch,
+                pos: self.pos,
+            })
+        }
+    }
+
+    /// Returns the next token, recovering from lexing errors instead of aborting: each error is
+    /// recorded in `errors`, the offending character is skipped, and lexing resumes. Used by the
+    /// error-collecting [`parse_recover`](crate::shuntingyard::parse_recover) entry point.
+    pub fn next_token_recover(&mut self, errors: &mut Vec<ParseError>) -> Token<'a> {
+        loop {
+            match self.next_token_() {
+                Ok(token) => return token,
+                Err(err) => {
+                    errors.push(err);
+                    self.skip_char();
+                }
+            }
+        }
+    }
+
+    /// Advances past a single `char`, keeping `pos` in sync. A no-op at the end of input.
+    fn skip_char(&mut self) {
+        if let Some(ch) = self.text.chars().next() {
+            self.advance(ch.len_utf8());
         }
     }
 
@@ -193,7 +229,7 @@ mod test {
                     error = Some(err);
                     Token {
                         typ: Ident("<ERROR>"),
-                        pos: 0,
+                        pos: crate::error::Position::start(),
                     }
                 })
             })
@@ -210,6 +246,24 @@ mod test {
         println!("---------------------");
     }
 
+    #[test]
+    fn test_line_col_tracking() {
+        use super::Lexer;
+        use super::TokenType::Ident;
+
+        // The identifier on the second line should report line 2, and its column should count
+        // from the start of that line rather than from the start of the input.
+        let mut lexer = Lexer::new("x +\n  y");
+        let tokens: Vec<_> = lexer
+            .iter()
+            .map(|res| res.unwrap())
+            .collect();
+        let last = tokens.last().unwrap();
+        assert_eq!(last.typ, Ident("y"));
+        assert_eq!(last.pos.line, 2);
+        assert_eq!(last.pos.col, 3);
+    }
+
     #[test]
     fn test_lexer_iter_eq() {
         use super::TokenType::Ident;
@@ -224,7 +278,7 @@ mod test {
             .map(|res| {
                 res.unwrap_or_else(|_| Token {
                     typ: Ident("<ERROR>"),
-                    pos: 0,
+                    pos: crate::error::Position::start(),
                 })
             })
             .collect();
@@ -232,7 +286,7 @@ mod test {
             .map(|res| {
                 res.unwrap_or_else(|_| Token {
                     typ: Ident("<ERROR>"),
-                    pos: 0,
+                    pos: crate::error::Position::start(),
                 })
             })
             .collect();
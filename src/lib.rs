@@ -4,5 +4,6 @@ extern crate regex;
 
 pub mod ast;
 pub mod error;
+pub mod eval;
 pub mod lexer;
 pub mod shuntingyard;
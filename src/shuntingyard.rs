@@ -1,6 +1,6 @@
 use ast::AstNode;
 use ast::AstType::{Number, Ident, Func, Binary, Prefix, Postfix, Parens};
-use error::ParseError;
+use error::{ParseError, Position, Span};
 use lexer::{Lexer, Token, TokenType};
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -19,26 +19,34 @@ enum Assoc { Left, Right }
 
 #[derive(Debug)]
 struct Op {
-    ch: char,
+    name: &'static str,
     typ: OpType,
     prec: u32,
     assoc: Assoc,
 }
 
 static SENTINEL: Op =
-    Op { ch: '\0', typ: OpType::Sentinel, prec: 0, assoc: Assoc::Left };
-
-static OPS: [Op; 7] = [
-    Op { ch: '+', typ: OpType::Binary,  prec: 1, assoc: Assoc::Left  },
-    Op { ch: '-', typ: OpType::Binary,  prec: 1, assoc: Assoc::Left  },
-    Op { ch: '*', typ: OpType::Binary,  prec: 2, assoc: Assoc::Left  },
-    Op { ch: '/', typ: OpType::Binary,  prec: 2, assoc: Assoc::Left  },
-    Op { ch: '-', typ: OpType::Prefix,  prec: 3, assoc: Assoc::Left  },
-    Op { ch: '!', typ: OpType::Postfix, prec: 4, assoc: Assoc::Left  },
-    Op { ch: '^', typ: OpType::Binary,  prec: 5, assoc: Assoc::Right },
+    Op { name: "", typ: OpType::Sentinel, prec: 0, assoc: Assoc::Left };
+
+// Precedence increases downward. Logical operators bind loosest, then comparisons, then the
+// arithmetic operators as before.
+static OPS: [Op; 13] = [
+    Op { name: "||", typ: OpType::Binary,  prec: 1, assoc: Assoc::Left  },
+    Op { name: "&&", typ: OpType::Binary,  prec: 2, assoc: Assoc::Left  },
+    Op { name: "==", typ: OpType::Binary,  prec: 3, assoc: Assoc::Left  },
+    Op { name: "!=", typ: OpType::Binary,  prec: 3, assoc: Assoc::Left  },
+    Op { name: "<=", typ: OpType::Binary,  prec: 3, assoc: Assoc::Left  },
+    Op { name: ">=", typ: OpType::Binary,  prec: 3, assoc: Assoc::Left  },
+    Op { name: "+",  typ: OpType::Binary,  prec: 4, assoc: Assoc::Left  },
+    Op { name: "-",  typ: OpType::Binary,  prec: 4, assoc: Assoc::Left  },
+    Op { name: "*",  typ: OpType::Binary,  prec: 5, assoc: Assoc::Left  },
+    Op { name: "/",  typ: OpType::Binary,  prec: 5, assoc: Assoc::Left  },
+    Op { name: "-",  typ: OpType::Prefix,  prec: 6, assoc: Assoc::Left  },
+    Op { name: "!",  typ: OpType::Postfix, prec: 7, assoc: Assoc::Left  },
+    Op { name: "^",  typ: OpType::Binary,  prec: 8, assoc: Assoc::Right },
 ];
 
-fn is_sentinel(op: Option<&(&Op, u32)>) -> bool {
+fn is_sentinel(op: Option<&(&Op, Position)>) -> bool {
     if let Some(&(&Op { typ: OpType::Sentinel, .. }, _)) = op {
         true
     } else {
@@ -46,8 +54,8 @@ fn is_sentinel(op: Option<&(&Op, u32)>) -> bool {
     }
 }
 
-fn get_op(op_char: char, typ: OpType) -> Option<&'static Op> {
-    OPS.iter().find(move |op| op.ch == op_char && op.typ == typ)
+fn get_op(name: &str, typ: OpType) -> Option<&'static Op> {
+    OPS.iter().find(move |op| op.name == name && op.typ == typ)
 }
 
 #[inline(always)]
@@ -61,8 +69,18 @@ fn has_greater_prec(op1: &Op, op2: &Op) -> bool {
 struct ShuntingYard<'a> {
     lexer: Lexer<'a>,
     next: Token<'a>,
-    op_stack: Vec<(&'static Op, u32)>, // (operator, position) pair
+    op_stack: Vec<(&'static Op, Position)>, // (operator, position) pair
     exp_stack: Vec<AstNode>,
+    errors: Vec<ParseError>, // diagnostics accumulated in recovery mode
+    recover: bool,           // whether to collect errors instead of bailing
+}
+
+fn is_operator_char(ch: char) -> bool {
+    let mut buf = [0u8; 4];
+    let s = ch.encode_utf8(&mut buf);
+    get_op(s, OpType::Binary).is_some()
+        || get_op(s, OpType::Prefix).is_some()
+        || get_op(s, OpType::Postfix).is_some()
 }
 
 impl<'a> ShuntingYard<'a> {
@@ -75,8 +93,71 @@ impl<'a> ShuntingYard<'a> {
     }
 
     fn consume(&mut self) -> Result<(), ParseError> {
-        self.next = try!(self.lexer.next_token());
-        Ok(())
+        if self.recover {
+            self.next = self.lexer.next_token_recover(&mut self.errors);
+            Ok(())
+        } else {
+            self.next = try!(self.lexer.next_token());
+            Ok(())
+        }
+    }
+
+    /// Best-effort parse that records every diagnostic rather than bailing at the first. On a
+    /// parse error it resynchronizes to the next operand or operator and keeps going, returning
+    /// the last fragment it managed to build (earlier fragments still contribute their own
+    /// diagnostics to `errors`, but only the final tree is returned).
+    fn recover_parse(&mut self) -> Option<AstNode> {
+        loop {
+            let start_pos = self.next.pos;
+            match self.parse_e() {
+                Ok(()) => {
+                    if self.next != TokenType::End {
+                        self.errors.push(ParseError::UnexpectedToken {
+                            found: format!("{:?}", self.next.typ),
+                            expected: None,
+                            span: Span::at(self.next.pos),
+                        });
+                    }
+                }
+                Err(err) => self.errors.push(err),
+            }
+            if self.next == TokenType::End {
+                break;
+            }
+            // Discard tokens up to the next operand, operator, or closing paren before retrying.
+            self.resync_discard();
+            if self.next == TokenType::End {
+                break;
+            }
+            // Force forward progress so recovery always terminates.
+            if self.next.pos == start_pos {
+                let _ = self.consume();
+            }
+            // Start the next fragment with a clean operator stack.
+            self.op_stack.clear();
+            self.op_stack.push((&SENTINEL, Position::start()));
+        }
+        self.exp_stack.pop()
+    }
+
+    /// Discards tokens until the next one can resume parsing: the start of an operand (a number,
+    /// identifier, or opening paren), an operator, a closing paren, a comma, or the end of input.
+    /// Without the operand case, valid tokens left over from a botched fragment (e.g. the `x` and
+    /// `4` in `3 @ x # 4`) would be silently eaten here instead of becoming their own fragment.
+    fn resync_discard(&mut self) {
+        loop {
+            match self.next.typ {
+                TokenType::End => return,
+                TokenType::Number(_) | TokenType::Ident(_) => return,
+                TokenType::OpSingle('(') => return,
+                TokenType::OpSingle(')') | TokenType::OpSingle(',') => return,
+                TokenType::OpMulti(_) => return,
+                TokenType::OpSingle(ch) if is_operator_char(ch) => return,
+                _ => {
+                    let _ = self.consume();
+                }
+            }
+        }
     }
 
     fn expect(&mut self, token_type: TokenType<'a>) -> Result<(), ParseError> {
@@ -84,30 +165,39 @@ impl<'a> ShuntingYard<'a> {
             try!(self.consume());
             Ok(())
         } else {
-            Err(ParseError::Parse(format!("Expected {:?} of expression, but got {:?} at position {:?}",
-                                          token_type, self.next.typ, self.next.pos)))
+            Err(ParseError::UnexpectedToken {
+                found: format!("{:?}", self.next.typ),
+                expected: Some(format!("{:?}", token_type)),
+                span: Span::at(self.next.pos),
+            })
         }
     }
 
     fn parse_e(&mut self) -> Result<(), ParseError> {
         try!(self.parse_p());
-        while let Token { typ: TokenType::OpSingle(ch), pos } = self.next {
-            if let Some(op) = get_op(ch, OpType::Binary) {
-                self.push_operator((op, pos));
+        loop {
+            let mut buf = [0u8; 4];
+            let (name, pos): (&str, Position) = match self.next {
+                Token { typ: TokenType::OpSingle(ch), pos } => (ch.encode_utf8(&mut buf), pos),
+                Token { typ: TokenType::OpMulti(s), pos } => (s, pos),
+                _ => break,
+            };
+            if let Some(op) = get_op(name, OpType::Binary) {
+                try!(self.push_operator((op, pos)));
                 try!(self.consume());
                 try!(self.parse_p());
-            } else if let Some(op) = get_op(ch, OpType::Postfix) {
-                self.push_operator((op, pos));
+            } else if let Some(op) = get_op(name, OpType::Postfix) {
+                try!(self.push_operator((op, pos)));
                 // The postfix operator's sole argument should be ready on the expression stack
                 // after push_operator completes, taking precedence into account.
-                self.pop_operator();
+                try!(self.pop_operator());
                 try!(self.consume());
             } else {
                 break;
             }
         }
         while !is_sentinel(self.op_stack.last()) {
-            self.pop_operator()
+            try!(self.pop_operator())
         }
         Ok(())
     }
@@ -122,8 +212,8 @@ impl<'a> ShuntingYard<'a> {
                 try!(self.consume());
                 if self.match_starting_parens() {
                     // Function call
-                    let t = try!(self.parse_parens(pos));
-                    self.exp_stack.push(AstNode::new(Func(s.to_string(), t), pos));
+                    let args = try!(self.parse_arg_list(pos));
+                    self.exp_stack.push(AstNode::new(Func(s.to_string(), args), pos));
                 } else {
                     // Identifier
                     self.exp_stack.push(AstNode::new(Ident(s.to_string()), pos));
@@ -134,16 +224,25 @@ impl<'a> ShuntingYard<'a> {
                 self.exp_stack.push(AstNode::new(Parens(t), pos));
             },
             &Token { typ: TokenType::OpSingle(ch), pos } => {
-                if let Some(op) = get_op(ch, OpType::Prefix) {
-                    self.push_operator((op, pos));
+                let mut buf = [0u8; 4];
+                if let Some(op) = get_op(ch.encode_utf8(&mut buf), OpType::Prefix) {
+                    try!(self.push_operator((op, pos)));
                     try!(self.consume());
                     try!(self.parse_p());
                 } else {
-                    return Err(ParseError::Parse(format!("Expected unary operator, but got {:?}", ch)));
+                    return Err(ParseError::UnexpectedToken {
+                        found: format!("{:?}", ch),
+                        expected: Some("unary operator".to_string()),
+                        span: Span::at(pos),
+                    });
                 }
             },
             _ => {
-                return Err(ParseError::Parse(format!("Unexpected token {:?}", self.next)));
+                return Err(ParseError::UnexpectedToken {
+                    found: format!("{:?}", self.next.typ),
+                    expected: None,
+                    span: Span::at(self.next.pos),
+                });
             }
         }
         Ok(())
@@ -153,39 +252,89 @@ impl<'a> ShuntingYard<'a> {
         if let &Token { typ: TokenType::OpSingle('('), pos: _ } = &self.next { true } else { false }
     }
 
-    fn parse_parens(&mut self, pos: u32) -> Result<Box<AstNode>, ParseError> {
+    /// Parses a comma-separated argument list `"(" [E {"," E}] ")"` for a function call.
+    /// Zero arguments (e.g. `pi()`) are allowed.
+    fn parse_arg_list(&mut self, pos: Position) -> Result<Vec<AstNode>, ParseError> {
+        assert!(self.match_starting_parens());
+        try!(self.consume());
+        let mut args = Vec::new();
+        if self.next == TokenType::OpSingle(')') {
+            try!(self.consume());
+            return Ok(args);
+        }
+        loop {
+            self.op_stack.push((&SENTINEL, pos));
+            try!(self.parse_e());
+            self.op_stack.pop().unwrap();
+            args.push(self.exp_stack.pop().unwrap());
+            if self.next == TokenType::OpSingle(',') {
+                try!(self.consume());
+            } else {
+                break;
+            }
+        }
+        if self.next == TokenType::OpSingle(')') {
+            try!(self.consume());
+        } else {
+            return Err(ParseError::UnbalancedParen { pos: pos });
+        }
+        Ok(args)
+    }
+
+    fn parse_parens(&mut self, pos: Position) -> Result<Box<AstNode>, ParseError> {
         assert!(self.match_starting_parens());
         try!(self.consume());
         self.op_stack.push((&SENTINEL, pos));
         try!(self.parse_e());
-        try!(self.expect(TokenType::OpSingle(')')));
+        if self.next == TokenType::OpSingle(')') {
+            try!(self.consume());
+        } else {
+            return Err(ParseError::UnbalancedParen { pos: pos });
+        }
         self.op_stack.pop().unwrap();
         Ok(Box::new(self.exp_stack.pop().unwrap()))
     }
 
-    fn top_operator(&mut self) -> &(&'static Op, u32) {
+    fn top_operator(&mut self) -> &(&'static Op, Position) {
         self.op_stack.last().unwrap()
     }
 
-    fn pop_operator(&mut self) {
+    /// Combines the top operator on `op_stack` with its operand(s) on `exp_stack`. Returns an
+    /// error instead of panicking if an operand is missing -- this can happen when two operators
+    /// of equal precedence are combined before any operand has been pushed between them, e.g. the
+    /// second `-` in `- -3` (prefix `-` is left-associative, so it is popped at equal precedence
+    /// as if it already had a left operand, which prefix operators never do).
+    fn pop_operator(&mut self) -> Result<(), ParseError> {
         let (op, pos) = self.op_stack.pop().unwrap();
-        let t = Box::new(self.exp_stack.pop().unwrap());
+        let t = Box::new(try!(self.pop_operand(pos)));
         match op {
-            &Op { ch, typ: OpType::Binary, .. } => {
-                let t0 = Box::new(self.exp_stack.pop().unwrap());
-                self.exp_stack.push(AstNode::new(Binary(ch, t0, t), pos));
+            &Op { name, typ: OpType::Binary, .. } => {
+                let t0 = Box::new(try!(self.pop_operand(pos)));
+                self.exp_stack.push(AstNode::new(Binary(name.to_string(), t0, t), pos));
             },
-            &Op { ch, typ: OpType::Prefix , .. } => self.exp_stack.push(AstNode::new(Prefix(ch, t), pos)),
-            &Op { ch, typ: OpType::Postfix, .. } => self.exp_stack.push(AstNode::new(Postfix(ch, t), pos)),
+            &Op { name, typ: OpType::Prefix , .. } => self.exp_stack.push(AstNode::new(Prefix(name.chars().next().unwrap(), t), pos)),
+            &Op { name, typ: OpType::Postfix, .. } => self.exp_stack.push(AstNode::new(Postfix(name.chars().next().unwrap(), t), pos)),
             &Op { typ: OpType::Sentinel, .. } => panic!("Unexpected Sentinel from position {:?} on operator stack", pos),
         }
+        Ok(())
     }
 
-    fn push_operator(&mut self, op_pos: (&'static Op, u32)) {
+    /// Pops a single operand off `exp_stack`, or an `UnexpectedToken` diagnostic at `pos` if the
+    /// stack is empty (a dangling operator with no operand to apply to).
+    fn pop_operand(&mut self, pos: Position) -> Result<AstNode, ParseError> {
+        self.exp_stack.pop().ok_or_else(|| ParseError::UnexpectedToken {
+            found: "operator".to_string(),
+            expected: Some("operand".to_string()),
+            span: Span::at(pos),
+        })
+    }
+
+    fn push_operator(&mut self, op_pos: (&'static Op, Position)) -> Result<(), ParseError> {
         while has_greater_prec(self.top_operator().0, op_pos.0) {
-           self.pop_operator();
+           try!(self.pop_operator());
         }
         self.op_stack.push(op_pos);
+        Ok(())
     }
 }
 
@@ -195,8 +344,8 @@ impl<'a> ShuntingYard<'a> {
 ///   https://www.engr.mun.ca/~theo/Misc/exp_parsing.htm
 /// It parses the following grammar:
 ///   E --> P {B P}
-///   P --> "(" E ")" | U P | P V | ident "(" P ")" | ident | number
-///   B --> "+" | "-" | "*" | "/" | "^"
+///   P --> "(" E ")" | U P | P V | ident "(" [E {"," E}] ")" | ident | number
+///   B --> "+" | "-" | "*" | "/" | "^" | "==" | "!=" | "<=" | ">=" | "&&" | "||"
 ///   U --> "-"
 ///   V --> "!"
 pub fn parse(text: &str) -> Result<AstNode, ParseError> {
@@ -207,13 +356,38 @@ pub fn parse(text: &str) -> Result<AstNode, ParseError> {
         next: next,
         op_stack: {
             let mut op_stack = Vec::new();
-            op_stack.push((&SENTINEL, 0));
+            op_stack.push((&SENTINEL, Position::start()));
             op_stack
         },
         exp_stack: Vec::new(),
+        errors: Vec::new(),
+        recover: false,
     }.parse()
 }
 
+/// Error-recovering variant of [`parse`] that never aborts on the first problem. It collects every
+/// lexer and parser diagnostic and returns them alongside a best-effort AST, which is `None` only
+/// when nothing at all could be parsed.
+pub fn parse_recover(text: &str) -> (Option<AstNode>, Vec<ParseError>) {
+    let mut lexer = Lexer::new(text);
+    let mut errors = Vec::new();
+    let next = lexer.next_token_recover(&mut errors);
+    let mut yard = ShuntingYard {
+        lexer: lexer,
+        next: next,
+        op_stack: {
+            let mut op_stack = Vec::new();
+            op_stack.push((&SENTINEL, Position::start()));
+            op_stack
+        },
+        exp_stack: Vec::new(),
+        errors: errors,
+        recover: true,
+    };
+    let ast = yard.recover_parse();
+    (ast, yard.errors)
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 
 #[cfg(test)]
@@ -229,5 +403,101 @@ mod test {
         println!("{}", ast_node);
         println!("{:?}", ast_node);
     }
+
+    #[test]
+    fn test_comparison_and_logical() {
+        use ast::AstType;
+
+        // Comparisons bind tighter than logical operators, both looser than arithmetic.
+        let ast_node = parse("x^2 >= 4 && y != 0").unwrap();
+        assert_eq!(format!("{}", ast_node), "x^2>=4&&y!=0");
+
+        // The flat Display string above doesn't distinguish between parses with different
+        // precedence/associativity, so also check the actual tree shape:
+        //   &&(>=(^(x, 2), 4), !=(y, 0))
+        let (and_op, ge_node, ne_node) = match &ast_node.typ {
+            &AstType::Binary(ref op, ref lhs, ref rhs) => (op, lhs, rhs),
+            _ => panic!("expected top-level Binary(\"&&\", ..)"),
+        };
+        assert_eq!(and_op, "&&");
+
+        let (ge_op, pow_node, four) = match &ge_node.typ {
+            &AstType::Binary(ref op, ref lhs, ref rhs) => (op, lhs, rhs),
+            _ => panic!("expected lhs of && to be Binary(\">=\", ..)"),
+        };
+        assert_eq!(ge_op, ">=");
+        match &four.typ {
+            &AstType::Number(n) => assert_eq!(n, 4.0),
+            _ => panic!("expected rhs of >= to be Number(4)"),
+        }
+        match &pow_node.typ {
+            &AstType::Binary(ref op, ref x, ref two) => {
+                assert_eq!(op, "^");
+                match &x.typ {
+                    &AstType::Ident(ref s) => assert_eq!(s, "x"),
+                    _ => panic!("expected lhs of ^ to be Ident(\"x\")"),
+                }
+                match &two.typ {
+                    &AstType::Number(n) => assert_eq!(n, 2.0),
+                    _ => panic!("expected rhs of ^ to be Number(2)"),
+                }
+            }
+            _ => panic!("expected lhs of >= to be Binary(\"^\", ..)"),
+        }
+
+        let (ne_op, y_node, zero) = match &ne_node.typ {
+            &AstType::Binary(ref op, ref lhs, ref rhs) => (op, lhs, rhs),
+            _ => panic!("expected rhs of && to be Binary(\"!=\", ..)"),
+        };
+        assert_eq!(ne_op, "!=");
+        match &y_node.typ {
+            &AstType::Ident(ref s) => assert_eq!(s, "y"),
+            _ => panic!("expected lhs of != to be Ident(\"y\")"),
+        }
+        match &zero.typ {
+            &AstType::Number(n) => assert_eq!(n, 0.0),
+            _ => panic!("expected rhs of != to be Number(0)"),
+        }
+    }
+
+    #[test]
+    fn test_function_args() {
+        assert_eq!(format!("{}", parse("atan2(y, x)").unwrap()), "atan2(y,x)");
+        assert_eq!(format!("{}", parse("max(a, b+1, c)").unwrap()), "max(a,b+1,c)");
+        assert_eq!(format!("{}", parse("pi()").unwrap()), "pi()");
+    }
+
+    #[test]
+    fn test_recover_collects_multiple_lex_errors() {
+        use super::parse_recover;
+        // Two stray characters should both be reported rather than only the first.
+        let (ast, errors) = parse_recover("3 @ x # 4");
+        assert!(errors.len() >= 2);
+        assert!(ast.is_some());
+    }
+
+    #[test]
+    fn test_recover_resync_preserves_subsequent_operands() {
+        use super::parse_recover;
+        // Before the resync fix, the stray "b" between the "a" and "+ c" fragments was eaten as
+        // raw discard fodder instead of becoming the start of the next fragment, leaving a
+        // spurious "unary operator expected" error on the dangling "+" and recovering only "c".
+        // It should instead resume right at "b", recovering "b+c" with a single trailing-token
+        // diagnostic for the leftover "a".
+        let (ast, errors) = parse_recover("a b + c");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(format!("{}", ast.unwrap()), "b+c");
+    }
+
+    #[test]
+    fn test_consecutive_prefix_minus_does_not_panic() {
+        // A dangling prefix `-` with no operand between it and the next `-` must be reported as
+        // a diagnostic, not crash the process.
+        assert!(parse("- -3").is_err());
+
+        let (ast, errors) = super::parse_recover("---");
+        assert!(!errors.is_empty());
+        assert!(ast.is_none());
+    }
 }
 